@@ -0,0 +1,18 @@
+#[path = "../importer.rs"]
+mod importer;
+#[path = "../queue.rs"]
+mod queue;
+#[path = "../types.rs"]
+mod types;
+
+use lambda_http::{tracing, Error};
+use sqlx::postgres::PgPool;
+use std::env;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing::init_default_subscriber();
+
+    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
+    queue::run_worker(pool).await
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use lambda_http::{Body, Error, Request, RequestExt, Response};
+use sqlx::postgres::PgPool;
+use sqlx::Row;
+use std::env;
+
+/// Escape the five XML predefined entities so issue titles can't break the feed
+/// markup (or inject elements) when embedded in an entry.
+pub fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Serve an Atom feed of a project's stored issues, optionally filtered to a
+/// single label (`?label=good-first-issue`). Complements the write-only import
+/// path so contributors can subscribe to a project in any feed reader.
+pub async fn serve(event: &Request) -> Result<Response<Body>, Error> {
+    let params = event.query_string_parameters();
+    let project_slug = params
+        .first("project")
+        .ok_or_else(|| Error::from("Missing project query parameter"))?;
+    let label = params.first("label");
+
+    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
+
+    let rows = sqlx::query(
+        "SELECT i.title, i.html_url, i.issue_created_at, i.issue_updated_at, i.user_login \
+         FROM issues i \
+         JOIN repositories r ON r.id = i.repository_id \
+         JOIN projects p ON p.id = r.project_id \
+         WHERE p.slug = $1 \
+         AND ($2::text IS NULL OR $2 = ANY(i.labels)) \
+         ORDER BY COALESCE(i.issue_updated_at, i.issue_created_at) DESC",
+    )
+    .bind(project_slug)
+    .bind(label)
+    .fetch_all(&pool)
+    .await?;
+
+    let mut entries = String::new();
+    let mut latest: Option<DateTime<Utc>> = None;
+    for row in &rows {
+        let title: String = row.get("title");
+        let created_at: DateTime<Utc> = row.get("issue_created_at");
+        // Both columns are nullable for rows written by the baseline importer;
+        // skip entries without a link target and fall back for a missing
+        // updated timestamp rather than panicking on a NULL decode.
+        let html_url: Option<String> = row.get("html_url");
+        let html_url = match html_url {
+            Some(html_url) => html_url,
+            None => continue,
+        };
+        let updated_at: Option<DateTime<Utc>> = row.get("issue_updated_at");
+        let updated_at = updated_at.unwrap_or(created_at);
+        // Author is required per RFC 4287; fall back when a legacy row has none.
+        let user_login: Option<String> = row.get("user_login");
+        let author = user_login.unwrap_or_else(|| "unknown".to_string());
+
+        latest = Some(latest.map_or(updated_at, |current| current.max(updated_at)));
+
+        entries.push_str(&format!(
+            "  <entry>\n\
+             \x20   <title>{}</title>\n\
+             \x20   <link href=\"{}\"/>\n\
+             \x20   <id>{}</id>\n\
+             \x20   <author><name>{}</name></author>\n\
+             \x20   <published>{}</published>\n\
+             \x20   <updated>{}</updated>\n\
+             \x20 </entry>\n",
+            xml_escape(&title),
+            xml_escape(&html_url),
+            xml_escape(&html_url),
+            xml_escape(&author),
+            created_at.to_rfc3339(),
+            updated_at.to_rfc3339(),
+        ));
+    }
+
+    let feed_title = match label {
+        Some(label) => format!("{} issues labelled {}", project_slug, label),
+        None => format!("{} issues", project_slug),
+    };
+    let updated = latest
+        .map(|updated| updated.to_rfc3339())
+        .unwrap_or_else(|| "1970-01-01T00:00:00+00:00".to_string());
+
+    // Stable tag: URI so the feed has the RFC 4287 required feed-level <id>.
+    let feed_id = match label {
+        Some(label) => format!("tag:kudos,2024:{}:{}", project_slug, label),
+        None => format!("tag:kudos,2024:{}", project_slug),
+    };
+
+    let feed = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         \x20 <title>{}</title>\n\
+         \x20 <id>{}</id>\n\
+         \x20 <updated>{}</updated>\n\
+         {}</feed>\n",
+        xml_escape(&feed_title),
+        xml_escape(&feed_id),
+        updated,
+        entries,
+    );
+
+    Ok(Response::builder()
+        .status(200)
+        .header("content-type", "application/atom+xml")
+        .body(Body::Text(feed))
+        .map_err(Box::new)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_all_predefined_entities() {
+        assert_eq!(
+            xml_escape(r#"a & b < c > d ' e " f"#),
+            "a &amp; b &lt; c &gt; d &apos; e &quot; f"
+        );
+    }
+
+    #[test]
+    fn leaves_plain_text_untouched() {
+        assert_eq!(xml_escape("good first issue"), "good first issue");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::types::{KudosIssue, Payload, RepoInfo, Repository};
+
+use chrono::{DateTime, Utc};
+use lambda_http::{tracing::error, Error};
+use octocrab::{params::State, Octocrab};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use sqlx::postgres::PgPool;
+use sqlx::Row;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Maximum number of repositories imported in parallel so a large payload
+/// doesn't open an unbounded number of GitHub/DB connections at once.
+const MAX_CONCURRENT_IMPORTS: usize = 6;
+
+/// Run a full import for a parsed payload against the given pool, returning the
+/// total number of issue rows imported. Shared by the HTTP handler (via the job
+/// queue) and the worker entrypoint.
+pub async fn run_import(pool: &PgPool, payload: Payload) -> Result<u64, Error> {
+    // get project id - need to ensure that name and slug are unique!
+    let project_row = sqlx::query("SELECT id FROM projects WHERE name = $1 AND slug = $2")
+        .bind(payload.project_name)
+        .bind(payload.project_slug)
+        .fetch_one(pool)
+        .await?;
+
+    let project_id: i32 = project_row.get("id");
+
+    if let Some(attributes) = payload.attributes {
+        sqlx::query("UPDATE projects SET purposes = $1, stack_levels = $2, technologies = $3, types = $4 WHERE id = $5")
+        .bind(attributes.purposes)
+        .bind(attributes.stack_levels)
+        .bind(attributes.technologies)
+        .bind(attributes.types)
+        .bind(project_id)
+        .execute(pool).await?;
+    }
+
+    for repo in payload.repos_to_remove {
+        // This should automatically cascade to issues table
+        sqlx::query("DELETE FROM repositories WHERE url = $1")
+            .bind(repo.url)
+            .execute(pool)
+            .await?;
+    }
+
+    if payload.repos_to_add.is_empty() {
+        return Ok(0);
+    }
+
+    let token = env::var("GITHUB_TOKEN")?;
+    let octocrab = Octocrab::builder().personal_token(token).build()?;
+
+    // Import the repositories concurrently with a bounded number of permits so
+    // fetches and inserts overlap instead of serializing one repo at a time.
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_IMPORTS));
+    let mut imports = FuturesUnordered::new();
+    for repo in payload.repos_to_add {
+        let octocrab = octocrab.clone();
+        let pool = pool.clone();
+        let semaphore = semaphore.clone();
+        imports.push(async move {
+            // Hold a permit for the lifetime of this repo's import.
+            let _permit = semaphore.acquire().await;
+            let url = repo.url.clone();
+            (url, import_repository(&octocrab, &pool, project_id, repo).await)
+        });
+    }
+
+    let mut total_issues_imported = 0;
+    while let Some((url, result)) = imports.next().await {
+        match result {
+            // A slow or failing repo must not abort the rest of the batch.
+            Ok(count) => total_issues_imported += count,
+            Err(e) => error!("Error importing repository {}: {}", url, e),
+        }
+    }
+
+    Ok(total_issues_imported)
+}
+
+/// Sync a single repository: upsert its row, fetch the issues changed since its
+/// last sync, delete the closed or no-longer-matching ones and upsert the rest,
+/// returning the number of issue rows upserted. Owns its own repository + issue
+/// queries so it can run independently of the other repositories in the payload.
+async fn import_repository(
+    octocrab: &Octocrab,
+    pool: &PgPool,
+    project_id: i32,
+    repo: Repository,
+) -> Result<u64, Error> {
+    let repo_info = RepoInfo::from_url(&repo.url)
+        .ok_or_else(|| Error::from("Couldn't extract repo info from url"))?;
+
+    // Identify the repository by its `owner/name` slug so two repos that share a
+    // human label don't collapse onto one row under UNIQUE(slug, project_id).
+    let repo_slug = format!("{}/{}", repo_info.owner, repo_info.name);
+
+    let repo_row = sqlx::query(repo.insert_respository_query())
+        .bind(&repo_slug)
+        .bind(project_id)
+        .fetch_one(pool)
+        .await?;
+    let repo_id: i32 = repo_row.get("id");
+    let last_synced_at: Option<DateTime<Utc>> = repo_row.get("last_synced_at");
+
+    // Fetch every state so we learn about issues that were closed since the
+    // last sync; `since` keeps the page set to just what changed.
+    let mut list = octocrab.issues(repo_info.owner, repo_info.name).list();
+    list = list.state(State::All).per_page(100);
+    if let Some(since) = last_synced_at {
+        list = list.since(since);
+    }
+    let page = list.send().await?;
+
+    // Drain every page so repositories with more than 100 changed issues are
+    // synced in full rather than silently truncated to the first page.
+    let issues = octocrab.all_pages(page).await?;
+
+    let kudos_issues: Vec<KudosIssue> = issues
+        .into_iter()
+        .filter_map(|issue| {
+            issue
+                .pull_request
+                .is_none()
+                .then(|| KudosIssue::from(issue))
+        })
+        .collect();
+
+    // An issue mirrors into the stored set only while it is open and still
+    // passes the repo's label filters. Everything else — closed, or no longer
+    // qualifying because it gained an exclude label or lost an include label —
+    // must be deleted so the stored set tracks the live set, not just skipped.
+    let (keep, drop): (Vec<KudosIssue>, Vec<KudosIssue>) = kudos_issues
+        .into_iter()
+        .partition(|issue| !issue.is_closed() && repo.matches_labels(&issue.labels));
+
+    if !drop.is_empty() {
+        let drop_numbers: Vec<i64> = drop.iter().map(|issue| issue.number).collect();
+        sqlx::query("DELETE FROM issues WHERE repository_id = $1 AND number = ANY($2)")
+            .bind(repo_id)
+            .bind(&drop_numbers)
+            .execute(pool)
+            .await?;
+    }
+
+    let mut upserted = 0;
+    if !keep.is_empty() {
+        let placeholders = keep
+            .iter()
+            .enumerate()
+            .map(|(i, _)| {
+                format!(
+                    "(${}, ${}, ${}, ${}, ${}, ${}, ${}, ${})",
+                    i * 8 + 1,
+                    i * 8 + 2,
+                    i * 8 + 3,
+                    i * 8 + 4,
+                    i * 8 + 5,
+                    i * 8 + 6,
+                    i * 8 + 7,
+                    i * 8 + 8
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Reconcile via upsert so a repeated sync updates existing rows in place
+        // instead of duplicating them or tripping the unique constraint.
+        let query_string = format!(
+            "INSERT INTO issues (number, title, labels, repository_id, issue_created_at, issue_updated_at, html_url, user_login) \
+             VALUES {} \
+             ON CONFLICT (repository_id, number) DO UPDATE SET \
+             title = EXCLUDED.title, labels = EXCLUDED.labels, \
+             issue_updated_at = EXCLUDED.issue_updated_at, html_url = EXCLUDED.html_url, \
+             user_login = EXCLUDED.user_login",
+            placeholders
+        );
+
+        let mut upsert_issues_query = sqlx::query(&query_string);
+
+        for issue in keep {
+            upsert_issues_query = upsert_issues_query
+                .bind(issue.number)
+                .bind(issue.title)
+                .bind(issue.labels)
+                .bind(repo_id)
+                .bind(issue.issue_created_at)
+                .bind(issue.issue_updated_at)
+                .bind(issue.html_url)
+                .bind(issue.user)
+        }
+
+        upserted = upsert_issues_query.execute(pool).await?.rows_affected();
+    }
+
+    // Advance the watermark so the next sync only pulls what changed after now.
+    sqlx::query("UPDATE repositories SET last_synced_at = now() WHERE id = $1")
+        .bind(repo_id)
+        .execute(pool)
+        .await?;
+
+    Ok(upserted)
+}
@@ -1,160 +1,100 @@
+mod feed;
+mod importer;
+mod queue;
 mod types;
 
-use types::{KudosIssue, Payload, RepoInfo};
+use types::Payload;
 
 use lambda_http::{
+    http::Method,
     run, service_fn,
     tracing::{self, error},
     Body, Error, Request, Response,
 };
-use octocrab::{params::State, Octocrab};
 use serde_json;
 
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use sqlx::postgres::PgPool;
-use sqlx::Row;
 use std::env;
 
-async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
-    let request_body = event.body();
-    let json_string = (match request_body {
-        Body::Text(json) => Some(json),
-        _ => None,
-    })
-    .ok_or_else(|| Error::from("Invalid request body type"))?;
-
-    let payload: Payload = serde_json::from_str(&json_string).map_err(|e| {
-        error!("Error parsing JSON: {}", e);
-        Error::from("Error parsing payload JSON")
-    })?;
-
-    let secret = &env::var("SECRET")?;
-    if payload.secret != *secret {
-        return Err(Error::from("Error: secrets don't match"));
-    }
+/// Header carrying the hex-encoded `sha256=` HMAC of the raw request body.
+const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Verify a GitHub-webhook-style signature over the raw body bytes. The header
+/// is `sha256=<hex>`; comparison is delegated to the MAC so it runs in constant
+/// time and doesn't leak where a forged signature diverges.
+fn verify_signature(body: &[u8], signature: Option<&str>, secret: &[u8]) -> bool {
+    let signature = match signature {
+        Some(value) => value,
+        None => return false,
+    };
+    let hex = match signature.strip_prefix("sha256=") {
+        Some(hex) => hex,
+        None => return false,
+    };
+    let provided = match hex::decode(hex) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&provided).is_ok()
+}
 
-    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
+/// Build a bare 401 response for requests whose signature is missing or invalid.
+fn unauthorized() -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(401)
+        .header("content-type", "text/plain")
+        .body(Body::Text("Invalid signature".to_string()))
+        .map_err(Box::new)?)
+}
 
-    // get project id - need to ensure that name and slug are unique!
-    let project_row = sqlx::query("SELECT id FROM projects WHERE name = $1 AND slug = $2")
-        .bind(payload.project_name)
-        .bind(payload.project_slug)
-        .fetch_one(&pool)
-        .await?;
-
-    let project_id: i32 = project_row.get("id");
-
-    if let Some(attributes) = payload.attributes {
-        sqlx::query("UPDATE projects SET purposes = $1, stack_levels = $2, technologies = $3, types = $4 WHERE id = $5")
-        .bind(attributes.purposes)
-        .bind(attributes.stack_levels)
-        .bind(attributes.technologies)
-        .bind(attributes.types)
-        .bind(project_id)
-        .execute(&pool).await?;
+async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
+    // GET serves the read-only Atom feed; everything else is a signed import.
+    if event.method() == Method::GET {
+        return feed::serve(&event).await;
     }
 
-    for repo in payload.repos_to_remove {
-        // This should automatically cascade to issues table
-        sqlx::query("DELETE FROM repositories WHERE url = $1")
-            .bind(repo.url)
-            .execute(&pool)
-            .await?;
+    // Verify the signature over the raw body bytes *before* parsing, so the
+    // check runs against exactly what the client signed.
+    let body_bytes: &[u8] = match event.body() {
+        Body::Text(json) => json.as_bytes(),
+        Body::Binary(bytes) => bytes.as_slice(),
+        Body::Empty => &[],
+    };
+
+    let secret = env::var("SECRET")?;
+    let signature = event
+        .headers()
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if !verify_signature(body_bytes, signature, secret.as_bytes()) {
+        error!("Rejecting request with missing or invalid signature");
+        return unauthorized();
     }
 
-    if payload.repos_to_add.is_empty() {
-        // return early
-        return Ok(Response::builder()
-            .status(200)
-            .header("content-type", "text/plain")
-            .body(Body::Text(format!(
-                "Total issues imported: {}",
-                "total_issues_imported"
-            )))
-            .map_err(Box::new)?);
-    }
+    let json_string = std::str::from_utf8(body_bytes)
+        .map_err(|_| Error::from("Invalid request body type"))?;
 
-    let token = env::var("GITHUB_TOKEN")?;
-    let octocrab = Octocrab::builder().personal_token(token).build()?;
-
-    let mut total_issues_imported = 0;
-    for repo in payload.repos_to_add {
-        let repo_info = RepoInfo::from_url(&repo.url)
-            .ok_or_else(|| Error::from("Couldn't extract repo info from url"))?;
-
-        let repo_row = sqlx::query(repo.insert_respository_query())
-            .bind(&repo.label)
-            .bind(project_id)
-            .fetch_one(&pool)
-            .await?;
-        let repo_id: i32 = repo_row.get("id");
-
-        let page = octocrab
-            .issues(repo_info.owner, repo_info.name)
-            .list()
-            .state(State::Open)
-            .per_page(100)
-            .send()
-            .await?;
-
-        let filtered_issues: Vec<KudosIssue> = page
-            .items
-            .into_iter()
-            .filter_map(|issue| {
-                issue
-                    .pull_request
-                    .is_none()
-                    .then(|| KudosIssue::from(issue))
-            })
-            .collect();
-
-        if filtered_issues.is_empty() {
-            continue;
-        }
-
-        let placeholders = filtered_issues
-            .iter()
-            .enumerate()
-            .map(|(i, _)| {
-                format!(
-                    "(${}, ${}, ${}, ${}, ${})",
-                    i * 5 + 1,
-                    i * 5 + 2,
-                    i * 5 + 3,
-                    i * 5 + 4,
-                    i * 5 + 5
-                )
-            })
-            .collect::<Vec<_>>()
-            .join(", ");
-
-        let query_string = format!(
-            "INSERT INTO issues (number, title, labels, repository_id, issue_created_at) VALUES {}",
-            placeholders
-        );
-
-        let mut insert_issues_query = sqlx::query(&query_string);
-
-        for issue in filtered_issues {
-            insert_issues_query = insert_issues_query
-                .bind(issue.number)
-                .bind(issue.title)
-                .bind(issue.labels)
-                .bind(repo_id)
-                .bind(issue.issue_created_at)
-        }
-
-        let issues_inserted_count = insert_issues_query.execute(&pool).await?.rows_affected();
-
-        total_issues_imported += issues_inserted_count;
-    }
+    let payload: Payload = serde_json::from_str(json_string).map_err(|e| {
+        error!("Error parsing JSON: {}", e);
+        Error::from("Error parsing payload JSON")
+    })?;
+
+    // Enqueue the import and return immediately; a worker drains the queue so a
+    // large sync can't exceed the Lambda/API Gateway response timeout.
+    let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
+    let job_id = queue::enqueue(&pool, queue::IMPORT_QUEUE, &payload).await?;
 
     let resp = Response::builder()
-        .status(200)
+        .status(202)
         .header("content-type", "text/plain")
-        .body(Body::Text(format!(
-            "Total issues imported: {}",
-            total_issues_imported
-        )))
+        .body(Body::Text(format!("Import queued: {}", job_id)))
         .map_err(Box::new)?;
     Ok(resp)
 }
@@ -165,3 +105,40 @@ async fn main() -> Result<(), Error> {
 
     run(service_fn(function_handler)).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // GitHub's documented HMAC-SHA256 webhook example.
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    const VALID: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        assert!(verify_signature(BODY, Some(VALID), SECRET));
+    }
+
+    #[test]
+    fn rejects_a_wrong_signature() {
+        let wrong = "sha256=0000000000000000000000000000000000000000000000000000000000000000";
+        assert!(!verify_signature(BODY, Some(wrong), SECRET));
+    }
+
+    #[test]
+    fn rejects_a_missing_prefix() {
+        let no_prefix = VALID.trim_start_matches("sha256=");
+        assert!(!verify_signature(BODY, Some(no_prefix), SECRET));
+    }
+
+    #[test]
+    fn rejects_bad_hex() {
+        assert!(!verify_signature(BODY, Some("sha256=zzzz"), SECRET));
+    }
+
+    #[test]
+    fn rejects_a_missing_header() {
+        assert!(!verify_signature(BODY, None, SECRET));
+    }
+}
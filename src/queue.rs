@@ -0,0 +1,185 @@
+use crate::importer::run_import;
+use crate::types::Payload;
+
+use lambda_http::{
+    tracing::{error, info},
+    Error,
+};
+use sqlx::postgres::PgPool;
+use sqlx::types::{Json, Uuid};
+use sqlx::Row;
+use std::time::Duration;
+
+/// Name of the queue used for repository import jobs.
+pub const IMPORT_QUEUE: &str = "import";
+
+/// A `running` job is considered stalled once its heartbeat is older than this,
+/// and is reset to `new` so another worker can retry it.
+const STALLED_AFTER_SECS: i64 = 120;
+
+/// Once a job has been attempted this many times it is moved to the `failed`
+/// dead-letter state instead of being retried, so a poison job can't spin a
+/// worker indefinitely.
+const MAX_ATTEMPTS: i32 = 5;
+
+/// How often a worker refreshes the heartbeat of the job it is processing.
+const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+
+/// How long the worker sleeps when it finds no job to claim.
+const IDLE_POLL_SECS: u64 = 5;
+
+/// Enqueue a payload as a `new` job and return its id. The payload is stored as
+/// JSONB so it survives redeploys and can be claimed by any worker.
+pub async fn enqueue(pool: &PgPool, queue: &str, payload: &Payload) -> Result<Uuid, Error> {
+    let row = sqlx::query(
+        "INSERT INTO job_queue (queue, job, status) VALUES ($1, $2, 'new') RETURNING id",
+    )
+    .bind(queue)
+    .bind(Json(payload))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.get("id"))
+}
+
+/// Claim a `new` job for a queue, marking it `running`. Jobs are picked in `id`
+/// order; ids are random UUIDs, so the claim order does not follow the order
+/// in which jobs were enqueued. Uses `FOR UPDATE SKIP LOCKED` so multiple
+/// workers can pull concurrently without ever handing the same job to two of
+/// them.
+pub async fn claim_next(pool: &PgPool, queue: &str) -> Result<Option<(Uuid, Payload)>, Error> {
+    let row = sqlx::query(
+        "UPDATE job_queue SET status = 'running', heartbeat = now(), attempts = attempts + 1 \
+         WHERE id = ( \
+             SELECT id FROM job_queue \
+             WHERE status = 'new' AND queue = $1 \
+             ORDER BY id FOR UPDATE SKIP LOCKED LIMIT 1 \
+         ) RETURNING id, job",
+    )
+    .bind(queue)
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => {
+            let id: Uuid = row.get("id");
+            let Json(payload): Json<Payload> = row.get("job");
+            Ok(Some((id, payload)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reset jobs whose worker died mid-flight so they are retried, or dead-letter
+/// them to `failed` once they have exhausted their attempts.
+pub async fn reset_stalled(pool: &PgPool) -> Result<u64, Error> {
+    let reset = sqlx::query(
+        "UPDATE job_queue \
+         SET status = CASE WHEN attempts >= $2 THEN 'failed'::job_status ELSE 'new'::job_status END \
+         WHERE status = 'running' \
+         AND heartbeat < now() - make_interval(secs => $1)",
+    )
+    .bind(STALLED_AFTER_SECS as f64)
+    .bind(MAX_ATTEMPTS)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(reset)
+}
+
+/// Release a job whose import failed: retry it unless it has exhausted its
+/// attempts, in which case move it to the `failed` dead-letter state.
+async fn release_failed(pool: &PgPool, id: Uuid) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE job_queue \
+         SET status = CASE WHEN attempts >= $2 THEN 'failed'::job_status ELSE 'new'::job_status END \
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(MAX_ATTEMPTS)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Refresh the heartbeat of a running job so it isn't mistaken for stalled.
+async fn touch_heartbeat(pool: &PgPool, id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE job_queue SET heartbeat = now() WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove a job once its import has completed successfully.
+async fn complete(pool: &PgPool, id: Uuid) -> Result<(), Error> {
+    sqlx::query("DELETE FROM job_queue WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Worker loop: reset stalled jobs, claim the next one, run the import while
+/// periodically refreshing the heartbeat, then delete the row on success. On
+/// failure the job is released back to `new` for another attempt, or moved to
+/// the `failed` dead-letter state once it has exhausted its attempts.
+pub async fn run_worker(pool: PgPool) -> Result<(), Error> {
+    loop {
+        if let Err(e) = reset_stalled(&pool).await {
+            error!("Error resetting stalled jobs: {}", e);
+        }
+
+        let claimed = match claim_next(&pool, IMPORT_QUEUE).await {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                error!("Error claiming job: {}", e);
+                tokio::time::sleep(Duration::from_secs(IDLE_POLL_SECS)).await;
+                continue;
+            }
+        };
+
+        let (id, payload) = match claimed {
+            Some(job) => job,
+            None => {
+                tokio::time::sleep(Duration::from_secs(IDLE_POLL_SECS)).await;
+                continue;
+            }
+        };
+
+        info!("Claimed job {}", id);
+
+        // Keep the heartbeat fresh for the duration of this job so a long import
+        // isn't reaped as stalled by another worker.
+        let heartbeat_pool = pool.clone();
+        let heartbeat = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = touch_heartbeat(&heartbeat_pool, id).await {
+                    error!("Error updating heartbeat for job {}: {}", id, e);
+                }
+            }
+        });
+
+        let result = run_import(&pool, payload).await;
+        heartbeat.abort();
+
+        match result {
+            Ok(count) => {
+                info!("Job {} imported {} issues", id, count);
+                if let Err(e) = complete(&pool, id).await {
+                    error!("Error completing job {}: {}", id, e);
+                }
+            }
+            Err(e) => {
+                error!("Error running job {}: {}", id, e);
+                // Retry until the attempt cap, then dead-letter the job.
+                if let Err(e) = release_failed(&pool, id).await {
+                    error!("Error releasing failed job {}: {}", id, e);
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use octocrab::models::issues::Issue;
+use octocrab::models::{issues::Issue, IssueState};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
@@ -31,6 +31,7 @@ pub struct KudosIssue {
     pub issue_updated_at: DateTime<Utc>,
     pub user: String,
     pub labels: Vec<String>,
+    pub state: String,
 }
 
 impl From<Issue> for KudosIssue {
@@ -47,28 +48,71 @@ impl From<Issue> for KudosIssue {
                 .iter()
                 .map(|label| label.name.clone())
                 .collect::<Vec<String>>(),
+            state: match value.state {
+                IssueState::Closed => "closed".to_string(),
+                _ => "open".to_string(),
+            },
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
+impl KudosIssue {
+    /// Whether GitHub reports this issue as closed, so the sync can drop it.
+    pub fn is_closed(&self) -> bool {
+        self.state == "closed"
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Repository {
     pub label: String,
     pub url: String,
+    #[serde(default)]
+    pub include_labels: Option<Vec<String>>,
+    #[serde(default)]
+    pub exclude_labels: Option<Vec<String>>,
 }
 
 impl Repository {
+    /// Whether an issue with these labels should surface to contributors: it
+    /// must carry at least one included label (when an include list is given)
+    /// and none of the excluded labels. Matching is case-insensitive.
+    pub fn matches_labels(&self, labels: &[String]) -> bool {
+        let has = |wanted: &str| {
+            labels
+                .iter()
+                .any(|label| label.eq_ignore_ascii_case(wanted))
+        };
+
+        if let Some(include) = &self.include_labels {
+            if !include.is_empty() && !include.iter().any(|label| has(label)) {
+                return false;
+            }
+        }
+
+        if let Some(exclude) = &self.exclude_labels {
+            if exclude.iter().any(|label| has(label)) {
+                return false;
+            }
+        }
+
+        true
+    }
+
     pub fn insert_respository_query(&self) -> &str {
+        // Upsert so re-syncing an existing repo reuses its row (and its
+        // last_synced_at watermark) instead of duplicating or conflicting.
         let query_string = r#"
         INSERT INTO repositories (slug, project_id)
         VALUES ($1, $2)
-        RETURNING id;
+        ON CONFLICT (slug, project_id) DO UPDATE SET slug = EXCLUDED.slug
+        RETURNING id, last_synced_at;
         "#;
         return query_string;
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectAttributes {
     pub purposes: Vec<String>,
@@ -77,12 +121,54 @@ pub struct ProjectAttributes {
     pub types: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Payload {
-    pub secret: String,
     pub project_slug: String,
     pub project_name: String,
     pub repos_to_add: Vec<Repository>,
     pub repos_to_remove: Vec<Repository>,
     pub attributes: Option<ProjectAttributes>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(include: Option<Vec<&str>>, exclude: Option<Vec<&str>>) -> Repository {
+        Repository {
+            label: "backend".to_string(),
+            url: "https://github.com/owner/repo".to_string(),
+            include_labels: include.map(|v| v.into_iter().map(String::from).collect()),
+            exclude_labels: exclude.map(|v| v.into_iter().map(String::from).collect()),
+        }
+    }
+
+    fn labels(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_everything_without_filters() {
+        assert!(repo(None, None).matches_labels(&labels(&["bug"])));
+    }
+
+    #[test]
+    fn requires_an_included_label_case_insensitively() {
+        let repo = repo(Some(vec!["good first issue"]), None);
+        assert!(repo.matches_labels(&labels(&["Good First Issue"])));
+        assert!(!repo.matches_labels(&labels(&["bug"])));
+    }
+
+    #[test]
+    fn rejects_an_excluded_label_case_insensitively() {
+        let repo = repo(None, Some(vec!["wontfix"]));
+        assert!(!repo.matches_labels(&labels(&["WontFix"])));
+        assert!(repo.matches_labels(&labels(&["help wanted"])));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let repo = repo(Some(vec!["help wanted"]), Some(vec!["blocked"]));
+        assert!(!repo.matches_labels(&labels(&["help wanted", "blocked"])));
+    }
+}